@@ -0,0 +1,87 @@
+// ctclsite-rust - CTCL 2020-2024
+// File: src/copymanifest.rs
+// Purpose: Track copied asset hashes so collectstatic/buildjs can skip up-to-date files
+// Created: July 26, 2026
+
+use crate::{read_file, write_file};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+const MANIFESTPATH: &str = "static/.copy_manifest.json";
+
+// Maps a relative path under static/ (e.g. "pages/index.md", "js/main.js") to the blake3
+// hash of the source file it was last copied from, so re-runs can skip files that have not
+// actually changed and prune copies whose source has since been deleted.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(transparent)]
+pub struct CopyManifest {
+    entries: HashMap<String, String>
+}
+
+impl CopyManifest {
+    pub fn load() -> CopyManifest {
+        match read_file(MANIFESTPATH) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_default(),
+            Err(_) => CopyManifest::default()
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let serialized = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string());
+        write_file(MANIFESTPATH, &serialized)
+    }
+
+    // Cheap modification-time check first; only skips straight to "up to date" when the
+    // destination is unambiguously newer than the source. On a tie, and when the source is
+    // newer than the destination (e.g. it was touched without its content changing), fall
+    // back to comparing content hashes rather than assuming staleness from mtime alone.
+    pub fn is_up_to_date(&self, relpath: &str, srcpath: &Path, destpath: &Path) -> bool {
+        let destmodified = match fs::metadata(destpath).and_then(|m| m.modified()) {
+            Ok(t) => t,
+            Err(_) => return false
+        };
+        let srcmodified = match fs::metadata(srcpath).and_then(|m| m.modified()) {
+            Ok(t) => t,
+            Err(_) => return false
+        };
+
+        if destmodified > srcmodified {
+            return true;
+        }
+
+        match self.entries.get(relpath) {
+            Some(hash) => hashfile(srcpath).as_deref() == Some(hash.as_str()),
+            None => false
+        }
+    }
+
+    pub fn record(&mut self, relpath: &str, srcpath: &Path) {
+        if let Some(hash) = hashfile(srcpath) {
+            self.entries.insert(relpath.to_string(), hash);
+        }
+    }
+
+    // Removes manifest entries for which `in_domain` returns true but that no longer appear
+    // in `present`, deleting their stale copy under static/ so deletions in the source tree
+    // are reflected. `in_domain` scopes the prune to one call site's slice of the manifest
+    // (e.g. only "pages/..." entries) so unrelated domains are left untouched.
+    pub fn prune(&mut self, present: &HashSet<String>, in_domain: impl Fn(&str) -> bool) {
+        let stale: Vec<String> = self.entries.keys()
+            .filter(|relpath| in_domain(relpath) && !present.contains(*relpath))
+            .cloned()
+            .collect();
+
+        for relpath in stale {
+            self.entries.remove(&relpath);
+            let _ = fs::remove_file(format!("static/{relpath}"));
+        }
+    }
+}
+
+fn hashfile(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(blake3::hash(&bytes).to_hex().to_string())
+}
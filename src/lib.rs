@@ -5,28 +5,45 @@
 // Modified: September 20, 2024
 
 //use minifier::js;
-//use minify_html::{minify, Cfg};
+use minify_html::{minify, Cfg};
 use comrak::{markdown_to_html, Options};
 use image::{Rgb, RgbImage};
 use indexmap::IndexMap;
 use log::{error, info, warn};
 use serde_json::value::Value;
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{Error, ErrorKind, Read, Write};
 use std::path::Path;
 use std::result::Result;
+use once_cell::sync::Lazy;
+use syntect::html::{highlighted_html_for_string, ClassStyle};
+use syntect::parsing::SyntaxSet;
+use syntect::highlighting::ThemeSet;
 use tera::{Context, Tera};
 use walkdir::WalkDir;
 
+// Dump-deserializing these is expensive, so build them once and reuse across every
+// mdpath2html call instead of per rendered markdown file.
+static SYNTAXSET: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+static THEMESET: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+
 pub mod themes;
 pub mod logger;
 pub mod page;
+pub mod search;
+pub mod watch;
+pub mod copymanifest;
+pub mod serve;
 
 pub use themes::*;
 pub use logger::*;
 pub use page::*;
+pub use search::*;
+pub use watch::*;
+pub use copymanifest::*;
+pub use serve::*;
 
 // To-Do: This file is long, consider splitting some code into modules
 
@@ -81,6 +98,11 @@ pub fn defaultfalse() -> bool {
     false
 }
 
+pub fn defaulthighlighttheme() -> String {
+    // Always a valid value, unlike a named syntect theme which may not exist in every build
+    "css".to_string()
+}
+
 pub fn defaulttrue() -> bool {
     true
 }
@@ -143,6 +165,15 @@ pub struct SiteConfig {
     pub log: LogConfig,
     // Exists solely for debugging purposes. It should be set to "true" in production.
     pub minimizehtml: bool,
+    // Name of a built-in syntect theme to use for fenced code block highlighting, or the
+    // literal "css" to emit semantic CSS classes instead so the active theme's stylesheet
+    // can color them.
+    #[serde(default = "defaulthighlighttheme")]
+    pub highlight_theme: String,
+    // When true, highlighted fenced code blocks are wrapped with a copy-to-clipboard button.
+    // Blocks with no language and inline code are left untouched either way.
+    #[serde(default = "defaultfalse")]
+    pub copybuttons: bool,
     // Definition of file types by file extension, used by collectstatic to determine what files to copy and may be used for the upcoming file viewer feature
     pub filetypes: HashMap<String, ExtensionFileType>,
     // Optional: Any extra parameters defined in config.json to be available in Lysine/Tera CSS templates
@@ -155,7 +186,34 @@ pub struct SiteConfig {
     #[serde(skip_deserializing, default = "emptythemehashmap")]
     pub themes: HashMap<String, Theme>,
     #[serde(skip_deserializing, default = "emptyfonthashmap")]
-    pub fonts: HashMap<String, FontFamily>
+    pub fonts: HashMap<String, FontFamily>,
+    // Where to target rendered pages and collected assets. "disk" writes under static/ as
+    // before; "memory" keeps them in `memstore` so Actix handlers can serve freshly rebuilt
+    // bytes directly, without a round trip through the filesystem.
+    #[serde(default = "defaultrenderbackend")]
+    pub renderbackend: RenderBackend,
+    // Only populated when renderbackend is "memory". Keyed by URL path.
+    #[serde(skip_deserializing, default = "emptymemorystore")]
+    pub memstore: MemoryStore
+}
+
+// URL path -> rendered bytes, used when renderbackend is RenderBackend::Memory
+pub type MemoryStore = std::sync::Arc<std::sync::RwLock<HashMap<String, Vec<u8>>>>;
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum RenderBackend {
+    #[serde(alias = "disk")]
+    Disk,
+    #[serde(alias = "memory")]
+    Memory
+}
+
+pub fn defaultrenderbackend() -> RenderBackend {
+    RenderBackend::Disk
+}
+
+pub fn emptymemorystore() -> MemoryStore {
+    std::sync::Arc::new(std::sync::RwLock::new(HashMap::new()))
 }
 
 // Partial config that only has fields for things required to start the webserver to avoid loading all of the pages twice
@@ -188,16 +246,60 @@ pub fn mkfavicons(themes: &HashMap<String, Theme>) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn buildjs(sitecfg: &SiteConfig) -> Result<(), Error> {
-    mkdir("static/js/")?;
+// Performs the copy when a ".code-copy-button" is clicked, using the Clipboard API against
+// the text of the sibling code block it was wrapped alongside by highlightcodeblocks.
+const COPYBUTTON_JS: &str = r#"(function () {
+    "use strict";
+    document.addEventListener("click", function (event) {
+        var button = event.target.closest(".code-copy-button");
+        if (!button) return;
+
+        var code = button.closest(".code-block").querySelector("pre");
+        if (!code) return;
+
+        navigator.clipboard.writeText(code.innerText).then(function () {
+            button.classList.add("code-copy-button-copied");
+            setTimeout(function () { button.classList.remove("code-copy-button-copied"); }, 2000);
+        });
+    });
+})();
+"#;
+
+pub fn buildjs(sitecfg: &SiteConfig, memstore: Option<&MemoryStore>) -> Result<(), Error> {
+    if memstore.is_none() {
+        mkdir("static/js/")?;
+    }
+
+    let mut manifest = CopyManifest::load();
+    let mut present: HashSet<String> = HashSet::new();
 
     match fs::read_dir(&sitecfg.jsdir) {
         Ok(d) => {
             for entry in d {
                 match entry {
-                    Ok(rd) => match fs::copy(rd.path(), format!("static/{}", rd.path().to_string_lossy())) {
-                        Ok(_) => (),
-                        Err(ce) => return Err(Error::new(ErrorKind::Other, format!("{ce}")))
+                    Ok(rd) => {
+                        let relpath = format!("js/{}", rd.file_name().to_string_lossy());
+                        present.insert(relpath.clone());
+
+                        match memstore {
+                            Some(store) => {
+                                let bytes = match fs::read(rd.path()) {
+                                    Ok(b) => b,
+                                    Err(ce) => return Err(Error::new(ErrorKind::Other, format!("{ce}")))
+                                };
+                                store.write().unwrap().insert(format!("/js/{}", rd.file_name().to_string_lossy()), bytes);
+                            }
+                            None => {
+                                let destpath = format!("static/{relpath}");
+                                if manifest.is_up_to_date(&relpath, &rd.path(), Path::new(&destpath)) {
+                                    continue;
+                                }
+                                match fs::copy(rd.path(), &destpath) {
+                                    Ok(_) => manifest.record(&relpath, &rd.path()),
+                                    Err(ce) => return Err(Error::new(ErrorKind::Other, format!("{ce}")))
+                                }
+                            }
+                        }
                     }
                     Err(re) => return Err(Error::new(ErrorKind::Other, format!("{re}")))
                 }
@@ -206,28 +308,61 @@ pub fn buildjs(sitecfg: &SiteConfig) -> Result<(), Error> {
         Err(e) => return Err(Error::new(ErrorKind::Other, format!("{e}")))
     }
 
+    if memstore.is_none() {
+        manifest.prune(&present, |relpath| relpath.starts_with("js/"));
+        manifest.save()?;
+    }
+
+    if sitecfg.copybuttons {
+        match memstore {
+            Some(store) => { store.write().unwrap().insert("/js/copybutton.js".to_string(), COPYBUTTON_JS.as_bytes().to_vec()); }
+            None => write_file("static/js/copybutton.js", COPYBUTTON_JS)?
+        }
+    }
+
     Ok(())
 }
 
-pub fn collectstatic(sitecfg: &SiteConfig) -> Result<(), Error> {
-    mkdir("static/pages/")?;
+pub fn collectstatic(sitecfg: &SiteConfig, memstore: Option<&MemoryStore>) -> Result<(), Error> {
+    if memstore.is_none() {
+        mkdir("static/pages/")?;
+    }
+
+    let mut manifest = CopyManifest::load();
+    let mut pagespresent: HashSet<String> = HashSet::new();
+    let mut staticpresent: HashSet<String> = HashSet::new();
 
     for entry in WalkDir::new(&sitecfg.pagedir).into_iter().filter_map(|e| e.ok()) {
-        if entry.path().is_dir() {
+        if memstore.is_none() && entry.path().is_dir() {
             match entry.path().to_string_lossy().strip_prefix(&sitecfg.pagedir) {
                 Some(p) => fs::create_dir_all(format!("static/pages/{}", p))?,
                 None => fs::create_dir_all(format!("static/pages/{}", entry.path().to_string_lossy()))?
             }
         }
-        
+
         if entry.path().is_file() {
             match entry.path().extension() {
                 Some(fp) => match sitecfg.filetypes.get(&fp.to_string_lossy().into_owned()) {
                     Some(f) => match f {
                         ExtensionFileType::Config => continue,
-                        _ => match entry.path().to_string_lossy().strip_prefix(&sitecfg.pagedir) {
-                            Some(p) => fs::copy(entry.path(), format!("static/pages/{}", p)).unwrap(),
-                            None => fs::copy(entry.path(), format!("static/pages/{}", entry.path().to_string_lossy())).unwrap()
+                        _ => {
+                            let relpath = entry.path().to_string_lossy().strip_prefix(&sitecfg.pagedir).map(|p| p.to_string()).unwrap_or_else(|| entry.path().to_string_lossy().to_string());
+                            let manifestpath = format!("pages/{relpath}");
+                            pagespresent.insert(manifestpath.clone());
+
+                            match memstore {
+                                // Derived from manifestpath (not rebuilt from relpath) so memory and disk
+                                // agree on the URL regardless of whether pagedir has a trailing slash.
+                                Some(store) => { store.write().unwrap().insert(format!("/{manifestpath}"), fs::read(entry.path()).unwrap()); }
+                                None => {
+                                    let destpath = format!("static/pages/{relpath}");
+                                    if manifest.is_up_to_date(&manifestpath, entry.path(), Path::new(&destpath)) {
+                                        continue;
+                                    }
+                                    fs::copy(entry.path(), &destpath).unwrap();
+                                    manifest.record(&manifestpath, entry.path());
+                                }
+                            };
                         }
                     }
                     None => continue
@@ -241,9 +376,29 @@ pub fn collectstatic(sitecfg: &SiteConfig) -> Result<(), Error> {
         Ok(d) => {
             for entry in d {
                 match entry {
-                    Ok(rd) => match fs::copy(rd.path(), format!("static/{}", rd.path().to_string_lossy().strip_prefix(&sitecfg.staticdir).unwrap())) {
-                        Ok(_) => (),
-                        Err(ce) => return Err(Error::new(ErrorKind::Other, format!("collectstatic failed to copy {} to static/{}, {}", rd.path().to_string_lossy(), rd.path().to_string_lossy(), ce)))
+                    Ok(rd) => {
+                        let relpath = rd.path().to_string_lossy().strip_prefix(&sitecfg.staticdir).unwrap().to_string();
+                        staticpresent.insert(relpath.clone());
+
+                        match memstore {
+                            Some(store) => {
+                                let bytes = match fs::read(rd.path()) {
+                                    Ok(b) => b,
+                                    Err(ce) => return Err(Error::new(ErrorKind::Other, format!("collectstatic failed to read {}, {}", rd.path().to_string_lossy(), ce)))
+                                };
+                                store.write().unwrap().insert(format!("/{relpath}"), bytes);
+                            }
+                            None => {
+                                let destpath = format!("static/{relpath}");
+                                if manifest.is_up_to_date(&relpath, &rd.path(), Path::new(&destpath)) {
+                                    continue;
+                                }
+                                match fs::copy(rd.path(), &destpath) {
+                                    Ok(_) => manifest.record(&relpath, &rd.path()),
+                                    Err(ce) => return Err(Error::new(ErrorKind::Other, format!("collectstatic failed to copy {} to static/{}, {}", rd.path().to_string_lossy(), rd.path().to_string_lossy(), ce)))
+                                }
+                            }
+                        }
                     }
                     Err(re) => return Err(Error::new(ErrorKind::Other, format!("collectstatic: {re}")))
                 }
@@ -252,6 +407,12 @@ pub fn collectstatic(sitecfg: &SiteConfig) -> Result<(), Error> {
         Err(e) => return Err(Error::new(ErrorKind::Other, format!("collectstatic: {e}")))
     }
 
+    if memstore.is_none() {
+        manifest.prune(&pagespresent, |relpath| relpath.starts_with("pages/"));
+        manifest.prune(&staticpresent, |relpath| !relpath.starts_with("pages/") && !relpath.starts_with("js/"));
+        manifest.save()?;
+    }
+
     Ok(())
 }
 
@@ -300,7 +461,28 @@ pub fn write_file<T: AsRef<Path>>(path: T, data: &str) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn mdpath2html(path: &str, headerids: bool) -> Result<String, Error> {
+// Minifies a fully-rendered page's HTML per spec: collapses insignificant whitespace and
+// drops optional tags/attributes only where valid, while preserving the contents of
+// <pre>/<textarea>/<script>/<style>. A no-op passthrough when `minimizehtml` is false so
+// debugging stays readable.
+pub fn minify_page_html(html: &str, minimizehtml: bool) -> String {
+    if !minimizehtml {
+        return html.to_string();
+    }
+
+    let mut cfg = Cfg::new();
+    cfg.minify_css = false;
+    cfg.minify_js = false;
+    cfg.keep_closing_tags = false;
+    cfg.keep_html_and_head_opening_tags = true;
+
+    match String::from_utf8(minify(html.as_bytes(), &cfg)) {
+        Ok(minified) => minified,
+        Err(_) => html.to_string()
+    }
+}
+
+pub fn mdpath2html(path: &str, headerids: bool, highlight_theme: &str, copybuttons: bool) -> Result<String, Error> {
     let mut comrak_options = Options::default();
     comrak_options.render.unsafe_ = true;
     comrak_options.extension.table = true;
@@ -312,14 +494,118 @@ pub fn mdpath2html(path: &str, headerids: bool) -> Result<String, Error> {
         Err(e) => return Err(Error::new(ErrorKind::Other, format!("Failed to render markdown file {path}: {e}")))
     };
     let content = markdown_to_html(&markdown, &comrak_options);
+    let content = highlightcodeblocks(&content, highlight_theme, copybuttons);
 
     Ok(content)
 }
 
+// Decodes the handful of HTML entities comrak escapes code block contents with, since
+// syntect needs the raw text to tokenize correctly.
+fn unescapehtml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+// Walks comrak's rendered HTML looking for `<pre><code class="language-...">` blocks and
+// highlights their contents by the fence's language token, mirroring Zola's highlight_theme
+// switch: a named syntect theme produces inline `style=` colors, while the literal "css"
+// produces semantic CSS classes so the active theme's stylesheet can color them instead.
+// Unknown languages and plain (unfenced) code blocks are left untouched. When `copybuttons`
+// is set, successfully highlighted blocks are wrapped with a copy-to-clipboard button.
+fn highlightcodeblocks(html: &str, highlight_theme: &str, copybuttons: bool) -> String {
+    const OPENTAG: &str = "<pre><code class=\"language-";
+    const CLOSETAG: &str = "</code></pre>";
+
+    let syntaxset = &*SYNTAXSET;
+    let themeset = &*THEMESET;
+
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(openidx) = rest.find(OPENTAG) {
+        out.push_str(&rest[..openidx]);
+
+        let aftertag = &rest[openidx + OPENTAG.len()..];
+        let langend = match aftertag.find('"') {
+            Some(i) => i,
+            None => {
+                out.push_str(&rest[openidx..]);
+                rest = "";
+                break;
+            }
+        };
+        let lang = &aftertag[..langend];
+
+        let aftergt = match aftertag[langend..].find('>') {
+            Some(i) => langend + i + 1,
+            None => {
+                out.push_str(&rest[openidx..]);
+                rest = "";
+                break;
+            }
+        };
+        let body = &aftertag[aftergt..];
+
+        let closeidx = match body.find(CLOSETAG) {
+            Some(i) => i,
+            None => {
+                out.push_str(&rest[openidx..]);
+                rest = "";
+                break;
+            }
+        };
+        let code = unescapehtml(&body[..closeidx]);
+
+        let syntax = syntaxset.find_syntax_by_token(lang);
+        match syntax {
+            Some(syntax) if highlight_theme == "css" => {
+                let html_generator = syntect::html::ClassedHTMLGenerator::new_with_class_style(syntax, syntaxset, ClassStyle::Spaced);
+                out.push_str(&maybewrapcopybutton(&render_classed_block(html_generator, &code), copybuttons));
+            }
+            Some(syntax) => match themeset.themes.get(highlight_theme) {
+                Some(theme) => match highlighted_html_for_string(&code, syntaxset, syntax, theme) {
+                    Ok(highlighted) => out.push_str(&maybewrapcopybutton(&highlighted, copybuttons)),
+                    Err(_) => out.push_str(&format!("<pre><code class=\"language-{lang}\">{}</code></pre>", &body[..closeidx]))
+                },
+                None => out.push_str(&format!("<pre><code class=\"language-{lang}\">{}</code></pre>", &body[..closeidx]))
+            },
+            // Unknown language: fall back to the escaped-but-unhighlighted output comrak already produced
+            None => out.push_str(&format!("<pre><code class=\"language-{lang}\">{}</code></pre>", &body[..closeidx]))
+        }
+
+        rest = &body[closeidx + CLOSETAG.len()..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn render_classed_block(mut generator: syntect::html::ClassedHTMLGenerator, code: &str) -> String {
+    for line in code.lines() {
+        let _ = generator.parse_html_for_line_which_includes_newline(&format!("{line}\n"));
+    }
+    format!("<pre class=\"code\"><code>{}</code></pre>", generator.finalize())
+}
+
+// Wraps a highlighted <pre> block with a theme-styleable copy-to-clipboard button, the
+// companion script for which is emitted by buildjs. No-op passthrough when disabled.
+fn maybewrapcopybutton(pre_html: &str, copybuttons: bool) -> String {
+    if !copybuttons {
+        return pre_html.to_string();
+    }
+
+    format!("<div class=\"code-block\">{pre_html}<button type=\"button\" class=\"code-copy-button\" aria-label=\"Copy code to clipboard\">Copy</button></div>")
+}
+
 pub fn loadconfig() -> Result<SiteConfig, Error> {
     let mut siteconfig: SiteConfig = serde_json::from_str(&read_file("ctclsite-config/config.json").unwrap()).unwrap();
 
-    mkdir("static/")?;
+    if siteconfig.renderbackend == RenderBackend::Disk {
+        mkdir("static/")?;
+    }
 
     siteconfig.fonts = match loadfonts(&siteconfig) {
         Ok(t) => t,
@@ -332,15 +618,24 @@ pub fn loadconfig() -> Result<SiteConfig, Error> {
         Err(e) => return Err(e)
     };
 
-    mkfavicons(&siteconfig.themes)?;
-    match collectstatic(&siteconfig) {
+    if siteconfig.highlight_theme != "css" && !THEMESET.themes.contains_key(&siteconfig.highlight_theme) {
+        error!("loadconfig: highlight_theme \"{}\" does not exist", siteconfig.highlight_theme);
+        return Err(Error::new(ErrorKind::NotFound, format!("highlight_theme \"{}\" does not exist", siteconfig.highlight_theme)));
+    }
+
+    let memstore = if siteconfig.renderbackend == RenderBackend::Memory { Some(siteconfig.memstore.clone()) } else { None };
+
+    if siteconfig.renderbackend == RenderBackend::Disk {
+        mkfavicons(&siteconfig.themes)?;
+    }
+    match collectstatic(&siteconfig, memstore.as_ref()) {
         Ok(_) => (),
         Err(e) => return Err(Error::new(ErrorKind::Other, format!("collectstatic: {e}")))
     };
 
     // Catch-22: Pages must be loaded to load pages in order to fill in linklist entries with information of a page
     siteconfig.pages = loadpages(&siteconfig)?;
-    
+
     if siteconfig.pages.is_empty() {
         error!("No pages found");
         return Err(Error::new(ErrorKind::NotFound, "No pages found"));
@@ -350,5 +645,10 @@ pub fn loadconfig() -> Result<SiteConfig, Error> {
         return Err(Error::new(ErrorKind::NotFound, "No themes found"));
     }
 
+    match buildsearchindex(&siteconfig) {
+        Ok(_) => (),
+        Err(e) => return Err(Error::new(ErrorKind::Other, format!("buildsearchindex: {e}")))
+    };
+
     Ok(siteconfig)
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+// ctclsite-rust - CTCL 2020-2024
+// File: src/page.rs
+// Purpose: Page loading and markdown-to-HTML rendering
+// Created: July 26, 2026
+
+use crate::{mdpath2html, minify_page_html, read_file, RenderBackend, SiteConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use walkdir::WalkDir;
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Page {
+    pub title: String,
+    pub url: String,
+    // Rendered HTML, filled in by loadpages. Empty for configonly/redirect pages.
+    #[serde(default)]
+    pub content: String,
+    // Pages marked config/redirect-only have no body to render: loadpages leaves their
+    // content empty and buildsearchindex skips them.
+    #[serde(default)]
+    pub configonly: bool,
+    #[serde(default)]
+    pub redirect: Option<String>
+}
+
+// Walks pagedir for "page.json" manifests and renders the markdown sitting alongside each
+// one ("page.md") to HTML via mdpath2html.
+pub fn loadpages(sitecfg: &SiteConfig) -> Result<HashMap<String, Page>, Error> {
+    let mut pages = HashMap::new();
+
+    for entry in WalkDir::new(&sitecfg.pagedir).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_name() != "page.json" {
+            continue;
+        }
+
+        let pagedir = match entry.path().parent() {
+            Some(p) => p,
+            None => continue
+        };
+
+        let manifest = read_file(entry.path())?;
+        let mut page: Page = match serde_json::from_str(&manifest) {
+            Ok(p) => p,
+            Err(e) => return Err(Error::new(ErrorKind::Other, format!("loadpages: failed to parse {}: {e}", entry.path().to_string_lossy())))
+        };
+
+        if !page.configonly && page.redirect.is_none() {
+            let mdpath = pagedir.join("page.md");
+            let rendered = mdpath2html(&mdpath.to_string_lossy(), true, &sitecfg.highlight_theme, sitecfg.copybuttons)?;
+            page.content = minify_page_html(&rendered, sitecfg.minimizehtml);
+
+            // In memory mode the rendered HTML is what gets served directly by Actix
+            // handlers (see serve.rs), so it must land in memstore under the page's URL -
+            // collectstatic only ever handles page attachments, never the rendered page itself.
+            if sitecfg.renderbackend == RenderBackend::Memory {
+                sitecfg.memstore.write().unwrap().insert(page.url.clone(), page.content.clone().into_bytes());
+            }
+        }
+
+        pages.insert(page.url.clone(), page);
+    }
+
+    Ok(pages)
+}
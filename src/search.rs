@@ -0,0 +1,195 @@
+// ctclsite-rust - CTCL 2020-2024
+// File: src/search.rs
+// Purpose: Build a client-side full-text search index at build time
+// Created: July 26, 2026
+
+use crate::{mkdir, write_file, RenderBackend, SiteConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+// Tokens shorter than this are dropped, they are not useful for ranking
+const MINTOKENLEN: usize = 2;
+// Length, in characters, of the plain-text excerpt stored per document
+const EXCERPTLEN: usize = 200;
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "the", "and", "or", "but", "if", "of", "at", "by", "for", "in", "is", "it",
+    "on", "to", "as", "be", "are", "was", "were", "this", "that", "with", "from", "not"
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchDocument {
+    pub id: usize,
+    pub title: String,
+    pub url: String,
+    pub excerpt: String
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct SearchIndex {
+    // Term -> list of (document id, term frequency)
+    pub terms: HashMap<String, Vec<(usize, u32)>>,
+    pub documents: Vec<SearchDocument>
+}
+
+fn strip_html_tags(html: &str) -> String {
+    let mut plain = String::with_capacity(html.len());
+    let mut intag = false;
+    for c in html.chars() {
+        match c {
+            '<' => intag = true,
+            '>' => intag = false,
+            _ if intag => (),
+            _ => plain.push(c)
+        }
+    }
+    plain
+}
+
+fn tokenize(plain: &str) -> Vec<String> {
+    plain
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|t| t.to_lowercase())
+        .filter(|t| t.len() >= MINTOKENLEN && !STOPWORDS.contains(&t.as_str()))
+        .collect()
+}
+
+fn excerpt(plain: &str) -> String {
+    let trimmed = plain.trim();
+    match trimmed.char_indices().nth(EXCERPTLEN) {
+        Some((idx, _)) => format!("{}...", &trimmed[..idx]),
+        None => trimmed.to_string()
+    }
+}
+
+// Walks every loaded page, tokenizes its rendered content and writes the resulting
+// inverted index to static/search_index.json (or, with the memory render backend, into
+// memstore under "/search_index.json"). Pages marked config/redirect-only
+// are skipped since they have no meaningful body text to search.
+pub fn buildsearchindex(sitecfg: &SiteConfig) -> Result<(), Error> {
+    let mut index = SearchIndex::default();
+
+    for page in sitecfg.pages.values() {
+        if page.configonly || page.redirect.is_some() {
+            continue;
+        }
+
+        let plain = strip_html_tags(&page.content);
+        let docid = index.documents.len();
+
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        for token in tokenize(&plain) {
+            *frequencies.entry(token).or_insert(0) += 1;
+        }
+
+        for (token, frequency) in frequencies {
+            index.terms.entry(token).or_insert_with(Vec::new).push((docid, frequency));
+        }
+
+        index.documents.push(SearchDocument {
+            id: docid,
+            title: page.title.clone(),
+            url: page.url.clone(),
+            excerpt: excerpt(&plain)
+        });
+    }
+
+    let serialized = if index.documents.is_empty() {
+        // Guard so the frontend querier can still load valid JSON and degrade gracefully
+        "{}".to_string()
+    } else {
+        match serde_json::to_string(&index) {
+            Ok(s) => s,
+            Err(e) => return Err(Error::new(ErrorKind::Other, format!("buildsearchindex: failed to serialize index: {e}")))
+        }
+    };
+
+    if sitecfg.renderbackend == RenderBackend::Memory {
+        let mut store = sitecfg.memstore.write().unwrap();
+        store.insert("/search_index.json".to_string(), serialized.into_bytes());
+        store.insert("/js/search.js".to_string(), SEARCH_QUERIER_JS.as_bytes().to_vec());
+    } else {
+        write_file("static/search_index.json", &serialized)?;
+        // buildjs is what normally creates static/js/, but loadconfig runs this before
+        // buildjs (if it runs it at all), so this must not assume the directory exists.
+        mkdir("static/js/")?;
+        write_file("static/js/search.js", SEARCH_QUERIER_JS)?;
+    }
+
+    Ok(())
+}
+
+// Bundled querier: loads search_index.json, ranks documents by summed term
+// frequency for the query tokens, and renders a results list.
+const SEARCH_QUERIER_JS: &str = r#"(function () {
+    "use strict";
+
+    var STOPWORDS = ["a", "an", "the", "and", "or", "but", "if", "of", "at", "by", "for", "in", "is", "it", "on", "to", "as", "be", "are", "was", "were", "this", "that", "with", "from", "not"];
+
+    function tokenize(query) {
+        return query
+            .split(/[^a-z0-9]+/i)
+            .map(function (t) { return t.toLowerCase(); })
+            .filter(function (t) { return t.length >= 2 && STOPWORDS.indexOf(t) === -1; });
+    }
+
+    function search(index, query) {
+        var tokens = tokenize(query);
+        var scores = {};
+
+        tokens.forEach(function (token) {
+            var postings = index.terms ? index.terms[token] : null;
+            if (!postings) return;
+            postings.forEach(function (posting) {
+                var docid = posting[0];
+                var frequency = posting[1];
+                scores[docid] = (scores[docid] || 0) + frequency;
+            });
+        });
+
+        return Object.keys(scores)
+            .map(function (docid) { return { doc: index.documents[docid], score: scores[docid] }; })
+            .sort(function (a, b) { return b.score - a.score; });
+    }
+
+    function render(container, results) {
+        container.innerHTML = "";
+        if (results.length === 0) {
+            container.textContent = "No results found.";
+            return;
+        }
+        var list = document.createElement("ul");
+        list.className = "search-results";
+        results.forEach(function (result) {
+            var item = document.createElement("li");
+            var link = document.createElement("a");
+            link.href = result.doc.url;
+            link.textContent = result.doc.title;
+            var excerpt = document.createElement("p");
+            excerpt.textContent = result.doc.excerpt;
+            item.appendChild(link);
+            item.appendChild(excerpt);
+            list.appendChild(item);
+        });
+        container.appendChild(list);
+    }
+
+    function init(inputSelector, resultsSelector) {
+        var input = document.querySelector(inputSelector);
+        var container = document.querySelector(resultsSelector);
+        if (!input || !container) return;
+
+        fetch("/search_index.json")
+            .then(function (response) { return response.json(); })
+            .then(function (index) {
+                input.addEventListener("input", function () {
+                    if (!index.documents) return;
+                    render(container, search(index, input.value));
+                });
+            });
+    }
+
+    window.ctclsearch = { init: init };
+})();
+"#;
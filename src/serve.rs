@@ -0,0 +1,98 @@
+// ctclsite-rust - CTCL 2020-2024
+// File: src/serve.rs
+// Purpose: Actix handlers serving rendered pages from disk or the in-memory store, plus the
+//          watch-mode live-reload websocket endpoint
+// Created: July 26, 2026
+
+use crate::{watch, LIVERELOAD_JS, RenderBackend, SiteConfig};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use std::io::Error;
+use std::sync::{Arc, Mutex};
+use tera::Tera;
+use tokio::sync::broadcast;
+
+// Injects the live-reload websocket client just before </body> so edits show up without a
+// manual page refresh. A no-op passthrough when watch mode is not running.
+fn injectlivereload(html: &str, watchmode: bool) -> String {
+    if !watchmode {
+        return html.to_string();
+    }
+
+    let script = format!("<script>{LIVERELOAD_JS}</script>");
+    match html.rfind("</body>") {
+        Some(idx) => format!("{}{}{}", &html[..idx], script, &html[idx..]),
+        None => format!("{html}{script}")
+    }
+}
+
+async fn servepage(req: HttpRequest, sitecfg: web::Data<Arc<Mutex<SiteConfig>>>, watchmode: web::Data<bool>) -> HttpResponse {
+    let cfg = sitecfg.lock().unwrap();
+    let path = req.path();
+
+    let body = match cfg.renderbackend {
+        RenderBackend::Memory => cfg.memstore.read().unwrap().get(path).cloned(),
+        RenderBackend::Disk => std::fs::read(format!("static{path}")).ok()
+    };
+
+    match body {
+        Some(bytes) => match (**watchmode, String::from_utf8(bytes.clone())) {
+            (true, Ok(html)) => HttpResponse::Ok().content_type("text/html; charset=utf-8").body(injectlivereload(&html, true)),
+            _ => HttpResponse::Ok().content_type("text/html; charset=utf-8").body(bytes)
+        },
+        None => HttpResponse::NotFound().finish()
+    }
+}
+
+// Serves the websocket a browser tab's LIVERELOAD_JS client connects to. Each connection
+// subscribes to the shared broadcast channel and forwards every "reload" notification - sent
+// by watch() after a successful rebuild - to that tab so it can refresh itself.
+async fn livereloadws(req: HttpRequest, stream: web::Payload, reload: web::Data<broadcast::Sender<()>>) -> Result<HttpResponse, actix_web::Error> {
+    let (response, mut session, _msgstream) = actix_ws::handle(&req, stream)?;
+    let mut rx = reload.subscribe();
+
+    actix_web::rt::spawn(async move {
+        while rx.recv().await.is_ok() {
+            if session.text("reload").await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(response)
+}
+
+// Starts the Actix server that serves pages from disk or, when sitecfg.renderbackend is
+// RenderBackend::Memory, directly from the bytes loadpages/collectstatic wrote into memstore
+// - no round trip through the filesystem. The watch-mode entry point alongside loadconfig:
+// pass watchmode true to also spawn the filesystem watcher in the background, mount the
+// /__livereload websocket, and inject LIVERELOAD_JS into every served page. Production runs
+// should pass watchmode false so nothing watches the filesystem.
+pub async fn runserver(sitecfg: SiteConfig, watchmode: bool) -> std::io::Result<()> {
+    let bindip = sitecfg.bindip.clone();
+    let bindport = sitecfg.bindport;
+    let (reloadtx, _) = broadcast::channel::<()>(16);
+
+    let sitecfg = Arc::new(Mutex::new(sitecfg));
+    let tera = Arc::new(Mutex::new(Tera::default()));
+
+    if watchmode {
+        let watchsitecfg = sitecfg.clone();
+        let watchtera = tera.clone();
+        let watchtx = reloadtx.clone();
+        std::thread::spawn(move || -> Result<(), Error> {
+            watch::watch(watchsitecfg, watchtera, move || { let _ = watchtx.send(()); })
+        });
+    }
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(sitecfg.clone()))
+            .app_data(web::Data::new(reloadtx.clone()))
+            .app_data(web::Data::new(watchmode))
+            .route("/__livereload", web::get().to(livereloadws))
+            .default_service(web::route().to(servepage))
+    })
+    .bind((bindip.as_str(), bindport))?
+    .run()
+    .await
+}
@@ -0,0 +1,102 @@
+// ctclsite-rust - CTCL 2020-2024
+// File: src/watch.rs
+// Purpose: Filesystem watching and live-reload for local development
+// Created: July 26, 2026
+
+use crate::{buildjs, buildsearchindex, collectstatic, loadfonts, loadpages, loadthemes, mkfavicons, SiteConfig};
+use log::{error, info, warn};
+use notify::RecursiveMode;
+use notify_debouncer_mini::new_debouncer;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tera::Tera;
+
+// Coalesces editor save storms into a single rebuild
+const DEBOUNCEWINDOW: Duration = Duration::from_millis(300);
+
+// Tiny websocket client injected into served HTML in watch mode. It listens for a
+// "reload" message and refreshes the page so edits show up without a manual restart.
+pub const LIVERELOAD_JS: &str = r#"(function () {
+    "use strict";
+    var socket = new WebSocket("ws://" + window.location.host + "/__livereload");
+    socket.addEventListener("message", function (event) {
+        if (event.data === "reload") {
+            window.location.reload();
+        }
+    });
+})();
+"#;
+
+// Watches pagedir, themedir, fontdir, jsdir, staticdir and templatedir for changes and
+// re-runs the whole build (fonts, themes, favicons, collectstatic, buildjs, pages and the
+// search index) on a debounced change event, whichever directory changed. `tera` is
+// re-parsed from templatedir on every rebuild so template edits take effect without a manual
+// restart. `onreload` is called after each successful rebuild so the caller (the Actix
+// server) can broadcast a "reload" message to connected livereload websockets. This is
+// opt-in: runserver spawns it in a background thread only when watchmode is true, so runs
+// with watchmode false never watch the filesystem.
+pub fn watch(sitecfg: Arc<Mutex<SiteConfig>>, tera: Arc<Mutex<Tera>>, onreload: impl Fn() + Send + 'static) -> Result<(), Error> {
+    let (tx, rx) = channel();
+
+    let mut debouncer = match new_debouncer(DEBOUNCEWINDOW, tx) {
+        Ok(d) => d,
+        Err(e) => return Err(Error::new(ErrorKind::Other, format!("watch: failed to create debouncer: {e}")))
+    };
+
+    {
+        let cfg = sitecfg.lock().unwrap();
+        for dir in [&cfg.pagedir, &cfg.themedir, &cfg.fontdir, &cfg.jsdir, &cfg.staticdir, &cfg.templatedir] {
+            if let Err(e) = debouncer.watcher().watch(Path::new(dir), RecursiveMode::Recursive) {
+                warn!("watch: failed to watch {dir}: {e}");
+            }
+        }
+    }
+
+    info!("watch: watching for filesystem changes");
+
+    for result in rx {
+        match result {
+            Ok(_) => {
+                info!("watch: change detected, rebuilding");
+
+                let mut cfg = sitecfg.lock().unwrap();
+                if let Err(e) = rebuild(&mut cfg) {
+                    error!("watch: rebuild failed: {e}");
+                    continue;
+                }
+
+                match Tera::new(&format!("{}/**/*", cfg.templatedir)) {
+                    Ok(t) => *tera.lock().unwrap() = t,
+                    Err(e) => {
+                        error!("watch: failed to reload templates: {e}");
+                        continue;
+                    }
+                };
+
+                onreload();
+            }
+            Err(e) => error!("watch: watch error: {e}")
+        }
+    }
+
+    Ok(())
+}
+
+fn rebuild(sitecfg: &mut SiteConfig) -> Result<(), Error> {
+    let memstore = if sitecfg.renderbackend == crate::RenderBackend::Memory { Some(sitecfg.memstore.clone()) } else { None };
+
+    sitecfg.fonts = loadfonts(sitecfg)?;
+    sitecfg.themes = loadthemes(sitecfg)?;
+    if memstore.is_none() {
+        mkfavicons(&sitecfg.themes)?;
+    }
+    collectstatic(sitecfg, memstore.as_ref())?;
+    buildjs(sitecfg, memstore.as_ref())?;
+    sitecfg.pages = loadpages(sitecfg)?;
+    buildsearchindex(sitecfg)?;
+
+    Ok(())
+}